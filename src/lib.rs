@@ -2,8 +2,15 @@
 //! You may use this implementation as you please for your needs.
 //! Test hashes have been calculated with third party hashing tools
 //! like the ones that come with 7-zip.
+//!
+//! The core compression function and state are `no_std`-friendly and never touch the heap.
+//! The default `std` feature layers `File`/reader helpers and `String`-returning hex output
+//! on top for the common case.
 
-use std::{fs::File, io::{self, Read, Seek}};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+use std::{fs::File, io::{self, Read, Write}};
 
 pub struct SHA1 {
     h0: u32,
@@ -11,156 +18,158 @@ pub struct SHA1 {
     h2: u32,
     h3: u32,
     h4: u32,
+    // Partial 64-byte block waiting for enough data to be processed
+    buffer: [u8; 64],
+    // Amount of `buffer` currently filled with pending bytes
+    buffer_len: usize,
+    // Running count of bytes passed to `update` so far
+    total_len: u64,
 }
 
 impl SHA1 {
     const K: [u32; 4] = [0x5a827999, 0x6ed9eba1, 0x8f1bbcdc, 0xca62c1d6];
 
-    // Initializes the hash
-    fn new() -> Self {
+    /// Initializes an empty hashing context, ready to receive data through [`SHA1::update`].
+    pub fn new() -> Self {
         Self {
             h0: 0x67452301,
             h1: 0xefcdab89,
             h2: 0x98badcfe,
             h3: 0x10325476,
             h4: 0xc3d2e1f0,
+            buffer: [0; 64],
+            buffer_len: 0,
+            total_len: 0,
         }
     }
 
-    // Should not be used with large files as it takes up too much memory.
-    // Can be used with small files though.
-    fn hash_sha1(mut message: Vec<u8>) -> Self {
-        let mut h: SHA1 = Self::new();
-        // Preprocessing the message
-        // Calculate message length in bits and convert it into bytes
-        let len: [u8; 8] = ((message.len() * 8) as u64).to_be_bytes();
-
-        // Add bit 1 in the end of the message
-        // Because we are working with bytes and padding with zeroes anyway,
-        // this is the same as adding byte 0x80
-        message.push(0x80);
-
-        // Pad with zeroes until the last block is 448 bits (56 bytes) long
-        while message.len() % 64 != 56 {
-            message.push(0);
+    /// Feeds more data into the context, hashing each 64-byte block as soon as it fills up
+    /// and carrying any remainder over to the next call. Can be called any number of times
+    /// before [`SHA1::finalize`], so callers don't need the whole message in memory at once.
+    pub fn update(&mut self, data: &[u8]) {
+        self.total_len += data.len() as u64;
+
+        let mut offset = 0;
+        while offset < data.len() {
+            let space = 64 - self.buffer_len;
+            let take = space.min(data.len() - offset);
+
+            self.buffer[self.buffer_len..self.buffer_len + take]
+                .copy_from_slice(&data[offset..offset + take]);
+            self.buffer_len += take;
+            offset += take;
+
+            if self.buffer_len == 64 {
+                let block = self.buffer;
+                self.hash_block(&block);
+                self.buffer_len = 0;
+            }
         }
+    }
 
-        // Append the length of the original message in big endian order
-        for byte in len {
-            message.push(byte);
+    /// Applies the closing padding (the `0x80` bit, zero-padding to 56 mod 64, and the
+    /// big-endian bit length) to whatever remains in the buffer, hashes the last one or two
+    /// blocks, and returns the finished hash.
+    pub fn finalize(mut self) -> Self {
+        let bit_len: [u8; 8] = (self.total_len * 8).to_be_bytes();
+
+        // At most one full block of zero padding plus the 0x80 byte and the length suffix
+        let mut pad: [u8; 128] = [0; 128];
+        pad[0] = 0x80;
+        let mut pad_len = 1;
+        while (self.buffer_len + pad_len) % 64 != 56 {
+            pad_len += 1;
         }
+        pad[pad_len..pad_len + 8].copy_from_slice(&bit_len);
+        pad_len += 8;
 
-        // Length of the entire message should now be a multiple of 512 (64 bytes)
-        assert!(message.len() % 64 == 0);
-
-        // Amount of blocks
-        let blocks: usize = message.len() / 64;
-
-        // Process the blocks
-        for b in 0..blocks {
-            let range = (b * 64)..((b + 1) * 64);
-            let block_bytes = message[range].to_vec();
+        self.update(&pad[..pad_len]);
+        assert!(self.buffer_len == 0);
 
-            h.hash_block(block_bytes);
-        }
-
-        h
+        self
     }
 
-    /// Hashes entire file contents while not consuming memory too much.
-    /// Works with large files too.
-    pub fn hash_sha1_file(file: &mut File) -> Result<Self, io::Error> {
-        let blen: u64 = file.metadata()?.len() as u64;
-        let len: [u8; 8] = (blen * 8).to_be_bytes();
-        let mut h: SHA1 = SHA1::new();
-
-        // Count whole 512-bit blocks
-        let whole_blocks = blen / 64;
-
-        // Count the left over byte amount
-        let left_over = blen % 64;
-
-        // Create last block(s)
-        // There is a possibility that there will be 2 blocks added
-        let mut last_blocks: Vec<u8> = Vec::new();
-
-        // Read last bytes from file to last_blocks
-        file.seek(io::SeekFrom::Start(whole_blocks * 64))?;
-        let bytes_read = file.read_to_end(&mut last_blocks)?;
-
-        assert!(bytes_read == left_over as usize);
-
-        // Append bit '1'
-        last_blocks.push(0x80);
-        
-        // Pad with zeroes
-        while (last_blocks.len() % 64) != 56 {
-            last_blocks.push(0);
+    /// Computes HMAC-SHA1 (RFC 2104) of `message` under `key`.
+    ///
+    /// Keys longer than the 64-byte block size are first hashed down to 20 bytes;
+    /// shorter keys are zero-padded out to the block size.
+    pub fn hmac_sha1(key: &[u8], message: &[u8]) -> SHA1 {
+        const BLOCK_SIZE: usize = 64;
+
+        let mut key_block: [u8; BLOCK_SIZE] = [0; BLOCK_SIZE];
+        if key.len() > BLOCK_SIZE {
+            let mut key_hash: SHA1 = Self::new();
+            key_hash.update(key);
+            let hashed = key_hash.finalize().to_bytes();
+            key_block[..hashed.len()].copy_from_slice(&hashed);
+        } else {
+            key_block[..key.len()].copy_from_slice(key);
         }
-        
-        // Append with bit length
-        for byte in len {
-            last_blocks.push(byte);
-        }
-
-        assert!(last_blocks.len() % 64 == 0);
-        
-        // Return file cursor into the beginning of the file
-        file.seek(io::SeekFrom::Start(0))?;
 
-        // Hash the whole blocks from the file directly.
-        // The file cursor should move automatically after every read.
-        for _ in 0..whole_blocks {
-            let block_bytes: Vec<u8> = {
-                let mut buf: [u8; 64] = [0; 64];
-                file.read_exact(&mut buf)?;
-
-                buf.to_vec()
-            };
-
-            h.hash_block(block_bytes);
+        let mut ipad: [u8; BLOCK_SIZE] = [0; BLOCK_SIZE];
+        let mut opad: [u8; BLOCK_SIZE] = [0; BLOCK_SIZE];
+        for i in 0..BLOCK_SIZE {
+            ipad[i] = key_block[i] ^ 0x36;
+            opad[i] = key_block[i] ^ 0x5c;
         }
 
-        // Last we hash the last blocks
-        for b in 0..(last_blocks.len() / 64) {
-            let range = b * 64..(b + 1) * 64;
-            let block_bytes: Vec<u8> = last_blocks[range].to_vec();
-
-            h.hash_block(block_bytes);
-        }
+        let mut inner: SHA1 = Self::new();
+        inner.update(&ipad);
+        inner.update(message);
+        let inner_digest = inner.finalize().to_bytes();
 
-        // Return the hash
-        Ok(h)
+        let mut outer: SHA1 = Self::new();
+        outer.update(&opad);
+        outer.update(&inner_digest);
+        outer.finalize()
     }
 
-    /// Convert SHA1 to lowercase hexadecimal string
-    pub fn to_lhex(self) -> String {
-        let mut hex: String = String::new();
+    /// Serializes the five hash words into the raw 20-byte big-endian digest, without
+    /// consuming `self` the way `to_lhex`/`to_uhex` do.
+    pub fn to_bytes(&self) -> [u8; 20] {
+        let mut bytes = [0u8; 20];
 
-        hex.push_str(format!("{:08x}", self.h0).as_str());
-        hex.push_str(format!("{:08x}", self.h1).as_str());
-        hex.push_str(format!("{:08x}", self.h2).as_str());
-        hex.push_str(format!("{:08x}", self.h3).as_str());
-        hex.push_str(format!("{:08x}", self.h4).as_str());
+        bytes[0..4].copy_from_slice(&self.h0.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.h1.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.h2.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.h3.to_be_bytes());
+        bytes[16..20].copy_from_slice(&self.h4.to_be_bytes());
 
-        hex
+        bytes
     }
 
-    /// Convert SHA1 to uppercase hexadecimal string
-    pub fn to_uhex(self) -> String {
-        let mut hex: String = String::new();
+    /// Writes the digest as ASCII hex digits into a caller-provided buffer, so no allocation
+    /// is needed to format it. `upper` selects uppercase vs lowercase digits.
+    pub fn to_hex(&self, buf: &mut [u8; 40], upper: bool) {
+        const LOWER: &[u8; 16] = b"0123456789abcdef";
+        const UPPER: &[u8; 16] = b"0123456789ABCDEF";
+        let table = if upper { UPPER } else { LOWER };
 
-        hex.push_str(format!("{:08X}", self.h0).as_str());
-        hex.push_str(format!("{:08X}", self.h1).as_str());
-        hex.push_str(format!("{:08X}", self.h2).as_str());
-        hex.push_str(format!("{:08X}", self.h3).as_str());
-        hex.push_str(format!("{:08X}", self.h4).as_str());
+        for (i, byte) in self.to_bytes().iter().enumerate() {
+            buf[i * 2] = table[(byte >> 4) as usize];
+            buf[i * 2 + 1] = table[(byte & 0x0f) as usize];
+        }
+    }
 
-        hex
+    /// Builds a context from an already-computed state, as if `processed_len` bytes had
+    /// already been passed to [`SHA1::update`]. This is the foundation for resuming a
+    /// partially-computed hash (e.g. length-extension).
+    pub fn from_state(h: [u32; 5], processed_len: u64) -> SHA1 {
+        Self {
+            h0: h[0],
+            h1: h[1],
+            h2: h[2],
+            h3: h[3],
+            h4: h[4],
+            buffer: [0; 64],
+            buffer_len: 0,
+            total_len: processed_len,
+        }
     }
 
-    fn hash_block(&mut self, block: Vec<u8>) {
-        let mut words: Vec<u32> = Vec::new();
+    fn hash_block(&mut self, block: &[u8; 64]) {
+        // Message schedule, expanded from the block's 16 words up to 80
+        let mut words: [u32; 80] = [0; 80];
 
         // Initialize working variables
         let mut a: u32 = self.h0;
@@ -169,36 +178,22 @@ impl SHA1 {
         let mut d: u32 = self.h3;
         let mut e: u32 = self.h4;
 
-        // Convert the bytes in the block into words
-        for i in 0..16 {
+        // Read the block's bytes directly into the first 16 words
+        for (i, word) in words.iter_mut().enumerate().take(16) {
             let range = (i * 4)..((i + 1) * 4);
-            let word: u32 = {
-                let mut wbuf: [u8; 4] = [0; 4];
-                wbuf.clone_from_slice(&block[range]);
-                u32::from_be_bytes(wbuf)
-            };
-
-            words.push(word);
+            *word = u32::from_be_bytes(block[range].try_into().unwrap());
         }
 
-        // Check that the words list is exactly 16 words long at this point to catch possible errors
-        assert!(words.len() == 16);
-
         // Extend the words from 16 words to 80 words aka prepare message schedule
         for i in 16..80 {
-            let w: u32 =
+            words[i] =
                 (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
-            words.push(w);
         }
 
-        // Assert that the message schedule is 80 words
-        assert!(words.len() == 80);
-
         // Process the message schedule
-        for t in 0..80 {
+        for (t, &w) in words.iter().enumerate() {
             let k: u32;
             let f: u32;
-            let w: u32 = words[t];
 
             match t / 20 {
                 // 0 <= t <= 19
@@ -256,10 +251,118 @@ impl SHA1 {
     }
 }
 
+impl Default for SHA1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl SHA1 {
+    // Should not be used with large files as it takes up too much memory.
+    // Can be used with small files though.
+    fn hash_sha1(message: Vec<u8>) -> Self {
+        let mut h: SHA1 = Self::new();
+        h.update(&message);
+        h.finalize()
+    }
+
+    /// Hashes entire file contents while not consuming memory too much.
+    /// Works with large files too.
+    pub fn hash_sha1_file(file: &mut File) -> Result<Self, io::Error> {
+        Self::hash_reader(file)
+    }
+
+    /// Hashes any reader by streaming it through the incremental `update` path in fixed
+    /// 64-byte chunks. It needs neither `Seek` nor a known length up front, so it also works
+    /// on sockets, stdin, or other non-seekable sources.
+    pub fn hash_reader<R: Read>(reader: &mut R) -> io::Result<SHA1> {
+        let mut h: SHA1 = Self::new();
+        let mut buf: [u8; 64] = [0; 64];
+
+        loop {
+            let bytes_read = match reader.read(&mut buf) {
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if bytes_read == 0 {
+                break;
+            }
+
+            h.update(&buf[..bytes_read]);
+        }
+
+        Ok(h.finalize())
+    }
+
+    /// Convert SHA1 to lowercase hexadecimal string
+    pub fn to_lhex(self) -> String {
+        let mut buf: [u8; 40] = [0; 40];
+        self.to_hex(&mut buf, false);
+
+        std::str::from_utf8(&buf).unwrap().to_string()
+    }
+
+    /// Convert SHA1 to uppercase hexadecimal string
+    pub fn to_uhex(self) -> String {
+        let mut buf: [u8; 40] = [0; 40];
+        self.to_hex(&mut buf, true);
+
+        std::str::from_utf8(&buf).unwrap().to_string()
+    }
+
+    /// Performs a SHA-1 length-extension attack: given a digest and the byte length of the
+    /// (unknown) message that produced it, resumes hashing as if that message were still
+    /// being fed through `update`, then hashes `suffix` on top of it.
+    ///
+    /// Returns the forged digest together with the glue padding (the `0x80` byte, the
+    /// zero-padding to 56 mod 64, and the original big-endian bit length) that the original
+    /// message would have received, so callers can assemble the full forged message as
+    /// `original || glue || suffix`.
+    pub fn extend(prev_digest: [u8; 20], original_len: u64, suffix: &[u8]) -> (SHA1, Vec<u8>) {
+        let mut h: [u32; 5] = [0; 5];
+        for i in 0..5 {
+            let mut word: [u8; 4] = [0; 4];
+            word.copy_from_slice(&prev_digest[i * 4..i * 4 + 4]);
+            h[i] = u32::from_be_bytes(word);
+        }
+
+        // Glue padding the original message would have received at `original_len` bytes
+        let bit_len: [u8; 8] = (original_len * 8).to_be_bytes();
+        let mut glue: Vec<u8> = vec![0x80];
+        while (original_len as usize + glue.len()) % 64 != 56 {
+            glue.push(0);
+        }
+        glue.extend_from_slice(&bit_len);
+
+        let mut h = SHA1::from_state(h, original_len + glue.len() as u64);
+        h.update(suffix);
+
+        (h.finalize(), glue)
+    }
+}
+
+/// Lets the streaming context be fed with `io::copy` and friends, e.g.
+/// `io::copy(&mut source, &mut hasher)`.
+#[cfg(feature = "std")]
+impl Write for SHA1 {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
 pub trait HashSHA1 {
     fn sha1(&self) -> SHA1;
 }
 
+#[cfg(feature = "std")]
 impl HashSHA1 for String {
     fn sha1(&self) -> SHA1 {
         let msg: Vec<u8> = self.clone().into_bytes();
@@ -267,6 +370,7 @@ impl HashSHA1 for String {
     }
 }
 
+#[cfg(feature = "std")]
 impl HashSHA1 for str {
     fn sha1(&self) -> SHA1 {
         let msg: Vec<u8> = self.as_bytes().to_vec();
@@ -274,6 +378,7 @@ impl HashSHA1 for str {
     }
 }
 
+#[cfg(feature = "std")]
 impl HashSHA1 for [u8] {
     fn sha1(&self) -> SHA1 {
         let msg: Vec<u8> = self.to_vec();
@@ -281,6 +386,7 @@ impl HashSHA1 for [u8] {
     }
 }
 
+#[cfg(feature = "std")]
 impl HashSHA1 for &[u8] {
     fn sha1(&self) -> SHA1 {
         let msg: Vec<u8> = self.to_vec();
@@ -288,12 +394,14 @@ impl HashSHA1 for &[u8] {
     }
 }
 
+#[cfg(feature = "std")]
 impl HashSHA1 for Vec<u8> {
     fn sha1(&self) -> SHA1 {
         SHA1::hash_sha1(self.clone())
     }
 }
 
+#[cfg(feature = "std")]
 #[test]
 fn test_hash0() {
     let data = "abcdefg";
@@ -303,6 +411,7 @@ fn test_hash0() {
     assert_eq!(hash_str, compare_str);
 }
 
+#[cfg(feature = "std")]
 #[test]
 fn test_hash1() {
     let data = "1234567890";
@@ -312,6 +421,7 @@ fn test_hash1() {
     assert_eq!(hash_str, compare_str);
 }
 
+#[cfg(feature = "std")]
 #[test]
 fn test_file_hash0() -> Result<(), io::Error> {
     let mut file: File = File::open("test.txt")?;
@@ -323,13 +433,14 @@ fn test_file_hash0() -> Result<(), io::Error> {
     Ok(())
 }
 
+#[cfg(feature = "std")]
 #[test]
 fn test_file_hash1() -> Result<(), io::Error> {
     let hash_str: String = {
         let mut file: File = File::open("test.txt")?;
         let mut buf: Vec<u8> = Vec::new();
         file.read_to_end(&mut buf)?;
-        
+
         buf.sha1().to_lhex()
     };
 
@@ -338,4 +449,76 @@ fn test_file_hash1() -> Result<(), io::Error> {
     assert_eq!(hash_str, compare_str);
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_hmac_sha1() {
+    let hash_str = SHA1::hmac_sha1(b"key", b"The quick brown fox jumps over the lazy dog").to_lhex();
+    let compare_str: String = String::from("de7c9b85b8b78aa6bc8a7a36f70a90701c9db4d9");
+
+    assert_eq!(hash_str, compare_str);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_length_extension() {
+    let original: &[u8] = b"count=10&lat=37.351&user_id=1&long=-119.827&waffle=eggo";
+    let suffix: &[u8] = b"&admin=true";
+
+    let prev_digest: [u8; 20] = original.to_vec().sha1().to_bytes();
+    let (forged, glue) = SHA1::extend(prev_digest, original.len() as u64, suffix);
+
+    let mut full_message: Vec<u8> = original.to_vec();
+    full_message.extend_from_slice(&glue);
+    full_message.extend_from_slice(suffix);
+
+    assert_eq!(forged.to_bytes(), full_message.sha1().to_bytes());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_hash_reader() -> io::Result<()> {
+    let data = "abcdefg";
+    let mut reader: &[u8] = data.as_bytes();
+    let hash_str = SHA1::hash_reader(&mut reader)?.to_lhex();
+    let compare_str: String = String::from("2fb5e13419fc89246865e7a324f476ec624e8740");
+
+    assert_eq!(hash_str, compare_str);
+
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_write_impl() -> io::Result<()> {
+    let mut hasher: SHA1 = SHA1::new();
+    io::copy(&mut "abcdefg".as_bytes(), &mut hasher)?;
+    let hash_str = hasher.finalize().to_lhex();
+    let compare_str: String = String::from("2fb5e13419fc89246865e7a324f476ec624e8740");
+
+    assert_eq!(hash_str, compare_str);
+
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_to_hex_no_alloc() {
+    let digest = "abcdefg".sha1().to_bytes();
+    let h = SHA1::from_state(
+        [
+            u32::from_be_bytes(digest[0..4].try_into().unwrap()),
+            u32::from_be_bytes(digest[4..8].try_into().unwrap()),
+            u32::from_be_bytes(digest[8..12].try_into().unwrap()),
+            u32::from_be_bytes(digest[12..16].try_into().unwrap()),
+            u32::from_be_bytes(digest[16..20].try_into().unwrap()),
+        ],
+        0,
+    );
+
+    let mut buf: [u8; 40] = [0; 40];
+    h.to_hex(&mut buf, false);
+
+    assert_eq!(&buf, b"2fb5e13419fc89246865e7a324f476ec624e8740");
+}